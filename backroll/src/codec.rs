@@ -0,0 +1,208 @@
+use crate::input::GameInput;
+use crate::{BackrollError, Frame, MAX_PLAYERS};
+use bytes::{Buf, BufMut, BytesMut};
+use std::convert::TryFrom;
+use std::marker::PhantomData;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Size in bytes of a `GameInput<T>`'s player array on the wire.
+fn inputs_size<T>() -> usize {
+    MAX_PLAYERS * std::mem::size_of::<T>()
+}
+
+/// RLE-compresses runs of zero bytes in `data`. A `0x00` byte is always
+/// followed by a run length (1-255); any other byte is a literal.
+fn rle_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == 0 {
+            let mut run: u8 = 0;
+            while i < data.len() && data[i] == 0 && run < 255 {
+                run += 1;
+                i += 1;
+            }
+            out.push(0);
+            out.push(run);
+        } else {
+            out.push(data[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Inverse of [`rle_encode`]. Fails if the decompressed length doesn't
+/// match `expected_len`, which indicates a truncated or corrupt run.
+fn rle_decode(data: &[u8], expected_len: usize) -> Result<Vec<u8>, BackrollError> {
+    let mut out = Vec::with_capacity(expected_len);
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == 0 {
+            let run = *data
+                .get(i + 1)
+                .ok_or_else(|| BackrollError::MalformedBatch("truncated RLE run".to_string()))?;
+            out.resize(out.len() + run as usize, 0);
+            i += 2;
+        } else {
+            out.push(data[i]);
+            i += 1;
+        }
+    }
+    if out.len() != expected_len {
+        return Err(BackrollError::MalformedBatch(format!(
+            "expected {} decompressed bytes, got {}",
+            expected_len,
+            out.len()
+        )));
+    }
+    Ok(out)
+}
+
+fn xor_bytes(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b.iter()).map(|(x, y)| x ^ y).collect()
+}
+
+/// A [`tokio_util::codec`] `Encoder`/`Decoder` pair for batches of
+/// `GameInput<T>`.
+///
+/// Prediction already assumes a player repeats their last input, so each
+/// frame in a batch (after the first) is stored as an XOR delta against its
+/// predecessor with runs of zero bytes RLE-compressed, rather than as a full
+/// `[T; MAX_PLAYERS]` array. The batch is prefixed with a `u32` length so it
+/// can be pulled off a length-delimited stream.
+pub struct InputCodec<T> {
+    _marker: PhantomData<T>,
+}
+
+impl<T> InputCodec<T> {
+    pub fn new() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for InputCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: bytemuck::Pod + PartialEq> Encoder<Vec<GameInput<T>>> for InputCodec<T> {
+    type Error = BackrollError;
+
+    fn encode(&mut self, batch: Vec<GameInput<T>>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let Some(base) = batch.first() else {
+            return Err(BackrollError::MalformedBatch("empty batch".to_string()));
+        };
+        for pair in batch.windows(2) {
+            if pair[1].frame != pair[0].frame + 1 {
+                return Err(BackrollError::MalformedBatch(
+                    "batch frames are not contiguous".to_string(),
+                ));
+            }
+        }
+
+        let mut body = BytesMut::new();
+        body.put_u32_le(u32::try_from(batch.len()).unwrap());
+        body.put_i64_le(base.frame as i64);
+        body.put_u8(base.disconnected);
+        body.put_slice(bytemuck::bytes_of(&base.inputs));
+
+        for pair in batch.windows(2) {
+            let delta = xor_bytes(
+                bytemuck::bytes_of(&pair[1].inputs),
+                bytemuck::bytes_of(&pair[0].inputs),
+            );
+            let compressed = rle_encode(&delta);
+            body.put_u8(pair[1].disconnected);
+            body.put_u32_le(u32::try_from(compressed.len()).unwrap());
+            body.put_slice(&compressed);
+        }
+
+        dst.put_u32_le(u32::try_from(body.len()).unwrap());
+        dst.put_slice(&body);
+        Ok(())
+    }
+}
+
+impl<T: bytemuck::Pod + PartialEq> Decoder for InputCodec<T> {
+    type Item = Vec<GameInput<T>>;
+    type Error = BackrollError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < 4 {
+            return Ok(None);
+        }
+        let body_len = u32::from_le_bytes(src[0..4].try_into().unwrap()) as usize;
+        if src.len() < 4 + body_len {
+            return Ok(None);
+        }
+
+        src.advance(4);
+        let body = src.split_to(body_len);
+        let inputs_len = inputs_size::<T>();
+
+        let malformed = |reason: &str| BackrollError::MalformedBatch(reason.to_string());
+        if body.len() < 4 + 8 + 1 + inputs_len {
+            return Err(malformed("batch body shorter than its fixed header"));
+        }
+
+        let frame_count = u32::from_le_bytes(body[0..4].try_into().unwrap()) as usize;
+        if frame_count == 0 {
+            return Err(malformed("batch declares zero frames"));
+        }
+        // Each delta frame after the base needs at least a 1-byte
+        // `disconnected` mask and a 4-byte compressed-length prefix. Checking
+        // this before allocating stops a corrupt/adversarial `frame_count`
+        // (e.g. `u32::MAX`) from triggering a huge `Vec::with_capacity`.
+        const MIN_DELTA_FRAME_LEN: usize = 1 + 4;
+        let remaining = body.len() - (4 + 8 + 1 + inputs_len);
+        if remaining < (frame_count - 1) * MIN_DELTA_FRAME_LEN {
+            return Err(malformed(
+                "frame_count exceeds what the batch body could possibly contain",
+            ));
+        }
+
+        let base_frame = i64::from_le_bytes(body[4..12].try_into().unwrap()) as Frame;
+        let base_disconnected = body[12];
+        let base_inputs_bytes = &body[13..13 + inputs_len];
+        let base_inputs: [T; MAX_PLAYERS] = bytemuck::pod_read_unaligned(base_inputs_bytes);
+
+        let mut frames = Vec::with_capacity(frame_count);
+        frames.push(GameInput {
+            frame: base_frame,
+            disconnected: base_disconnected,
+            inputs: base_inputs,
+        });
+
+        let mut cursor = 13 + inputs_len;
+        while frames.len() < frame_count {
+            if body.len() < cursor + 1 + 4 {
+                return Err(malformed("truncated delta frame header"));
+            }
+            let disconnected = body[cursor];
+            let compressed_len =
+                u32::from_le_bytes(body[cursor + 1..cursor + 5].try_into().unwrap()) as usize;
+            cursor += 5;
+            if body.len() < cursor + compressed_len {
+                return Err(malformed("truncated delta frame payload"));
+            }
+            let delta = rle_decode(&body[cursor..cursor + compressed_len], inputs_len)?;
+            cursor += compressed_len;
+
+            let previous = bytemuck::bytes_of(&frames.last().unwrap().inputs);
+            let absolute = xor_bytes(&delta, previous);
+            let inputs: [T; MAX_PLAYERS] = bytemuck::pod_read_unaligned(&absolute);
+            let frame = frames.last().unwrap().frame + 1;
+            frames.push(GameInput {
+                frame,
+                disconnected,
+                inputs,
+            });
+        }
+
+        Ok(Some(frames))
+    }
+}
@@ -1,11 +1,13 @@
-use crate::{BackrollError, Frame, PlayerHandle, MAX_PLAYERS, MAX_ROLLBACK_FRAMES};
+use crate::{
+    BackrollError, Frame, PlayerHandle, MAX_PLAYERS, MAX_PREDICTION_FRAMES, MAX_ROLLBACK_FRAMES,
+};
 use std::convert::TryFrom;
 use tracing::debug;
 
 #[inline]
-fn previous_frame(offset: usize) -> usize {
+fn previous_frame(offset: usize, capacity: usize) -> usize {
     if offset == 0 {
-        MAX_ROLLBACK_FRAMES - 1
+        capacity - 1
     } else {
         offset - 1
     }
@@ -93,7 +95,16 @@ impl<T> FetchedInput<T> {
     }
 }
 
-pub struct InputQueue<T> {
+/// A ring-buffered queue of per-frame inputs for a single player.
+///
+/// `N` is the number of frames of input the queue can hold before the
+/// oldest confirmed frames must be discarded (see [`discard_confirmed_frames`]).
+/// Games that want a larger rollback horizon than [`MAX_ROLLBACK_FRAMES`] can
+/// instantiate `InputQueue<T, N>` with a bigger `N` instead of forking the crate.
+///
+/// [`discard_confirmed_frames`]: Self::discard_confirmed_frames
+/// [`MAX_ROLLBACK_FRAMES`]: crate::MAX_ROLLBACK_FRAMES
+pub struct InputQueue<T, const N: usize = MAX_ROLLBACK_FRAMES> {
     head: usize,
     tail: usize,
     length: usize,
@@ -105,14 +116,25 @@ pub struct InputQueue<T> {
     last_frame_requested: Frame,
 
     frame_delay: Frame,
+    max_prediction_frames: usize,
 
-    inputs: [FrameInput<T>; MAX_ROLLBACK_FRAMES],
+    inputs: [FrameInput<T>; N],
     prediction: FrameInput<T>,
 }
 
-impl<T: bytemuck::Zeroable + Clone + PartialEq> InputQueue<T> {
+impl<T: bytemuck::Zeroable + Clone + PartialEq, const N: usize> InputQueue<T, N> {
     #[allow(clippy::uninit_assumed_init)]
     pub fn new(frame_delay: Frame) -> Self {
+        Self::with_max_prediction_frames(frame_delay, MAX_PREDICTION_FRAMES)
+    }
+
+    /// Like [`new`], but allows overriding how many frames of prediction are
+    /// permitted before the queue is considered to be predicting too far
+    /// ahead of the last confirmed input.
+    ///
+    /// [`new`]: Self::new
+    #[allow(clippy::uninit_assumed_init)]
+    pub fn with_max_prediction_frames(frame_delay: Frame, max_prediction_frames: usize) -> Self {
         // This is necessary as Default is not defined on arrays of more
         // than 32 without a Copy trait bound.
         //
@@ -120,8 +142,8 @@ impl<T: bytemuck::Zeroable + Clone + PartialEq> InputQueue<T> {
         // Assuming Zeroable is implemented correctly, this should also never
         // panic, so a buffer will always correctly be allocated as a large
         // zeroed buffer.
-        let inputs: [FrameInput<T>; MAX_ROLLBACK_FRAMES] = {
-            let mut inputs: [FrameInput<T>; MAX_ROLLBACK_FRAMES] =
+        let inputs: [FrameInput<T>; N] = {
+            let mut inputs: [FrameInput<T>; N] =
                 unsafe { std::mem::MaybeUninit::uninit().assume_init() };
 
             for input in inputs.iter_mut() {
@@ -136,6 +158,7 @@ impl<T: bytemuck::Zeroable + Clone + PartialEq> InputQueue<T> {
             tail: 0,
             length: 0,
             frame_delay,
+            max_prediction_frames,
             first_frame: true,
             last_user_added_frame: super::NULL_FRAME,
             first_incorrect_frame: super::NULL_FRAME,
@@ -146,6 +169,13 @@ impl<T: bytemuck::Zeroable + Clone + PartialEq> InputQueue<T> {
         }
     }
 
+    /// The maximum number of frames of prediction this queue will tolerate
+    /// before the caller should stop simulating ahead of the last confirmed
+    /// input.
+    pub fn max_prediction_frames(&self) -> usize {
+        self.max_prediction_frames
+    }
+
     pub fn last_confirmed_frame(&self) -> Frame {
         debug!("returning last confirmed frame {}.", self.last_added_frame);
         self.last_added_frame
@@ -162,7 +192,7 @@ impl<T: bytemuck::Zeroable + Clone + PartialEq> InputQueue<T> {
 
     pub fn discard_confirmed_frames(&mut self, mut frame: Frame) {
         debug_assert!(!super::is_null(frame));
-        if super::is_null(self.last_frame_requested) {
+        if !super::is_null(self.last_frame_requested) {
             frame = std::cmp::min(frame, self.last_frame_requested)
         }
 
@@ -179,7 +209,7 @@ impl<T: bytemuck::Zeroable + Clone + PartialEq> InputQueue<T> {
 
             debug!("difference of {} frames.", offset);
 
-            self.tail = (self.tail + offset) % MAX_ROLLBACK_FRAMES;
+            self.tail = (self.tail + offset) % N;
             self.length -= offset;
         }
     }
@@ -202,8 +232,11 @@ impl<T: bytemuck::Zeroable + Clone + PartialEq> InputQueue<T> {
         debug_assert!(
             super::is_null(self.first_incorrect_frame) || frame < self.first_incorrect_frame
         );
-        let offset = usize::try_from(frame).unwrap() % MAX_ROLLBACK_FRAMES;
-        self.inputs.get(offset)
+        let offset = usize::try_from(frame).unwrap() % N;
+        // The slot at `offset` may hold a different, overwritten frame if
+        // `frame` has already been discarded from the ring buffer (or was
+        // never added), so confirm it actually matches before returning it.
+        self.inputs.get(offset).filter(|input| input.frame == frame)
     }
 
     pub fn get_input(&mut self, frame: Frame) -> FetchedInput<T> {
@@ -225,7 +258,7 @@ impl<T: bytemuck::Zeroable + Clone + PartialEq> InputQueue<T> {
             let offset = frame - self.inputs[self.tail].frame;
             let mut offset = usize::try_from(offset).unwrap();
             if offset < self.len() {
-                offset = (offset + self.tail) % MAX_ROLLBACK_FRAMES;
+                offset = (offset + self.tail) % N;
                 let input = self.inputs[offset].clone();
                 debug_assert!(input.frame == frame);
                 debug!("returning confirmed frame number {}.", input.frame);
@@ -244,9 +277,9 @@ impl<T: bytemuck::Zeroable + Clone + PartialEq> InputQueue<T> {
             } else {
                 debug!(
                     "basing new prediction frame from previously added frame (frame: {}).",
-                    self.inputs[previous_frame(self.head)].frame
+                    self.inputs[previous_frame(self.head, N)].frame
                 );
-                self.prediction = self.inputs[previous_frame(self.head)].clone();
+                self.prediction = self.inputs[previous_frame(self.head, N)].clone();
             }
             self.prediction.frame += 1;
         }
@@ -254,6 +287,11 @@ impl<T: bytemuck::Zeroable + Clone + PartialEq> InputQueue<T> {
         // If we've made it this far, we must be predicting.  Go ahead and
         // forward the prediction frame contents.  Be sure to return the
         // frame number requested by the client, though.
+        debug_assert!(
+            super::is_null(self.last_added_frame)
+                || usize::try_from(frame - self.last_added_frame).unwrap()
+                    <= self.max_prediction_frames
+        );
         let mut prediction = self.prediction.clone();
         prediction.frame = frame;
         debug!(
@@ -288,12 +326,12 @@ impl<T: bytemuck::Zeroable + Clone + PartialEq> InputQueue<T> {
     fn add_delayed_input(&mut self, frame: Frame, input: FrameInput<T>) {
         debug!("adding delayed input frame number {} to queue.", frame);
         debug_assert!(super::is_null(self.last_added_frame) || frame == self.last_added_frame + 1);
-        debug_assert!(frame == 0 || self.inputs[previous_frame(self.head)].frame == frame - 1);
+        debug_assert!(frame == 0 || self.inputs[previous_frame(self.head, N)].frame == frame - 1);
 
         // Add the frame to the back of the queue
         self.inputs[self.head] = input.clone();
         self.inputs[self.head].frame = frame;
-        self.head = (self.head + 1) % MAX_ROLLBACK_FRAMES;
+        self.head = (self.head + 1) % N;
         self.length += 1;
         self.first_frame = false;
         self.last_added_frame = frame;
@@ -322,7 +360,7 @@ impl<T: bytemuck::Zeroable + Clone + PartialEq> InputQueue<T> {
                 self.prediction.frame += 1;
             }
         }
-        debug_assert!(self.len() <= MAX_ROLLBACK_FRAMES);
+        debug_assert!(self.len() <= N);
     }
 
     fn advance_queue_head(&mut self, mut frame: Frame) -> Frame {
@@ -330,7 +368,7 @@ impl<T: bytemuck::Zeroable + Clone + PartialEq> InputQueue<T> {
         let mut expected_frame = if self.first_frame {
             0
         } else {
-            self.inputs[previous_frame(self.head)].frame + 1
+            self.inputs[previous_frame(self.head, N)].frame + 1
         };
         frame += self.frame_delay;
 
@@ -356,12 +394,12 @@ impl<T: bytemuck::Zeroable + Clone + PartialEq> InputQueue<T> {
             );
             self.add_delayed_input(
                 expected_frame,
-                self.inputs[previous_frame(self.head)].clone(),
+                self.inputs[previous_frame(self.head, N)].clone(),
             );
             expected_frame += 1;
         }
 
-        debug_assert!(frame == 0 || frame == self.inputs[previous_frame(self.head)].frame + 1);
+        debug_assert!(frame == 0 || frame == self.inputs[previous_frame(self.head, N)].frame + 1);
         frame
     }
 
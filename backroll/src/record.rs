@@ -0,0 +1,207 @@
+use crate::input::FrameInput;
+use crate::{BackrollError, Frame};
+use std::collections::VecDeque;
+use std::io;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+/// Size in bytes of the little-endian
+/// `{ secs: u32, micros: u32, len: u32, frame: i64 }` header that precedes
+/// every recorded frame's payload.
+const HEADER_LEN: usize = 20;
+
+/// A single recorded frame of input, along with how long after the
+/// recording started it was committed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recorded<T> {
+    pub frame: Frame,
+    pub elapsed: Duration,
+    pub input: T,
+}
+
+/// Writes a stream of [`FrameInput`]s to `W` as they're committed, tagging
+/// each with the wall-clock time elapsed since the recorder was created so
+/// that a replay can reproduce the original pacing.
+///
+/// Each record is a little-endian `{ secs: u32, micros: u32, len: u32, frame:
+/// i64 }` header followed by `len` bytes of the input, serialized via
+/// `bytemuck`.
+pub struct Recorder<T, W> {
+    writer: W,
+    start: Instant,
+    _marker: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod, W: io::Write> Recorder<T, W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            start: Instant::now(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Appends `input` to the log, stamped with the time elapsed since this
+    /// recorder was created.
+    pub fn record(&mut self, input: &FrameInput<T>) -> io::Result<()> {
+        let elapsed = self.start.elapsed();
+        let payload = bytemuck::bytes_of(&input.input);
+
+        let mut header = [0u8; HEADER_LEN];
+        header[0..4].copy_from_slice(&(elapsed.as_secs() as u32).to_le_bytes());
+        header[4..8].copy_from_slice(&elapsed.subsec_micros().to_le_bytes());
+        header[8..12].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+        header[12..20].copy_from_slice(&(input.frame as i64).to_le_bytes());
+
+        self.writer.write_all(&header)?;
+        self.writer.write_all(payload)
+    }
+}
+
+/// Incrementally parses a [`Recorder`]'s log back into [`Recorded`] frames.
+///
+/// Bytes can be fed in as they arrive from a file or socket via
+/// [`add_bytes`]; [`next_frame`] only returns a frame once its full header
+/// and payload have been buffered, leaving any trailing partial record for
+/// the next call.
+///
+/// [`add_bytes`]: Self::add_bytes
+/// [`next_frame`]: Self::next_frame
+pub struct Parser<T> {
+    buffer: VecDeque<u8>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> Default for Parser<T> {
+    fn default() -> Self {
+        Self {
+            buffer: VecDeque::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: bytemuck::Pod> Parser<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffers `bytes` for parsing by subsequent calls to [`next_frame`].
+    ///
+    /// [`next_frame`]: Self::next_frame
+    pub fn add_bytes(&mut self, bytes: &[u8]) {
+        self.buffer.extend(bytes.iter().copied());
+    }
+
+    /// Returns the next fully-buffered recorded frame, `None` if the header
+    /// or payload hasn't arrived in full yet, or a [`BackrollError`] if the
+    /// record that did arrive is corrupt (e.g. a payload length that doesn't
+    /// match `size_of::<T>()`, as would happen from a truncated log or one
+    /// recorded for a different input type).
+    pub fn next_frame(&mut self) -> Result<Option<Recorded<T>>, BackrollError> {
+        if self.buffer.len() < HEADER_LEN {
+            return Ok(None);
+        }
+
+        let header: Vec<u8> = self.buffer.iter().take(HEADER_LEN).copied().collect();
+        let secs = u32::from_le_bytes(header[0..4].try_into().unwrap());
+        let micros = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let len = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+        let frame = i64::from_le_bytes(header[12..20].try_into().unwrap()) as Frame;
+
+        if self.buffer.len() < HEADER_LEN + len {
+            return Ok(None);
+        }
+
+        // Consume the record's bytes regardless of whether it turns out to
+        // be valid, so a single corrupt record doesn't wedge the parser on
+        // every subsequent call.
+        self.buffer.drain(..HEADER_LEN);
+        let payload: Vec<u8> = self.buffer.drain(..len).collect();
+
+        if len != std::mem::size_of::<T>() {
+            return Err(BackrollError::MalformedBatch(format!(
+                "record payload is {len} bytes, expected size_of::<T>() == {}",
+                std::mem::size_of::<T>()
+            )));
+        }
+        if micros >= 1_000_000 {
+            return Err(BackrollError::MalformedBatch(format!(
+                "record micros field {micros} is not a valid sub-second value"
+            )));
+        }
+
+        Ok(Some(Recorded {
+            frame,
+            elapsed: Duration::new(secs as u64, micros * 1_000),
+            input: bytemuck::pod_read_unaligned(&payload),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, bytemuck::Zeroable, bytemuck::Pod)]
+    #[repr(C)]
+    struct TestInput {
+        buttons: u8,
+    }
+
+    #[test]
+    fn round_trips_recorded_frames() {
+        let mut log = Vec::new();
+        let mut recorder = Recorder::new(&mut log);
+        recorder
+            .record(&FrameInput {
+                frame: 0,
+                input: TestInput { buttons: 1 },
+            })
+            .unwrap();
+        recorder
+            .record(&FrameInput {
+                frame: 1,
+                input: TestInput { buttons: 2 },
+            })
+            .unwrap();
+
+        let mut parser = Parser::<TestInput>::new();
+        // Feed the log in two pieces; both are buffered before any frame
+        // is parsed.
+        let split = log.len() / 2;
+        parser.add_bytes(&log[..split]);
+        parser.add_bytes(&log[split..]);
+
+        let first = parser.next_frame().unwrap().unwrap();
+        assert_eq!(first.frame, 0);
+        assert_eq!(first.input, TestInput { buttons: 1 });
+
+        let second = parser.next_frame().unwrap().unwrap();
+        assert_eq!(second.frame, 1);
+        assert_eq!(second.input, TestInput { buttons: 2 });
+
+        assert!(parser.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_a_record_with_a_mismatched_payload_length() {
+        let mut log = Vec::new();
+        let mut recorder = Recorder::<TestInput, _>::new(&mut log);
+        recorder
+            .record(&FrameInput {
+                frame: 0,
+                input: TestInput { buttons: 1 },
+            })
+            .unwrap();
+        // Corrupt the `len` field in the header to no longer match
+        // `size_of::<TestInput>()`.
+        log[8..12].copy_from_slice(&2u32.to_le_bytes());
+        log.push(0);
+
+        let mut parser = Parser::<TestInput>::new();
+        parser.add_bytes(&log);
+
+        assert!(parser.next_frame().is_err());
+    }
+}
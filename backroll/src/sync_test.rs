@@ -0,0 +1,171 @@
+use crate::input::{FrameInput, InputQueue};
+use crate::{Frame, MAX_ROLLBACK_FRAMES};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Callbacks a [`SyncTestSession`] uses to drive the simulation it is
+/// fuzzing for determinism.
+///
+/// [`SyncTestSession`]: SyncTestSession
+pub trait SyncTestCallbacks<T> {
+    /// An opaque, cloneable snapshot of the simulation's state.
+    type State: Clone;
+
+    /// Snapshot the current simulation state.
+    fn save_state(&mut self) -> Self::State;
+
+    /// Restore the simulation to a previously saved state.
+    fn load_state(&mut self, state: &Self::State);
+
+    /// Advance the simulation by a single frame using the given input.
+    fn advance_frame(&mut self, input: &T);
+
+    /// Compute a checksum of the current simulation state. Two runs that
+    /// produce the same checksum for the same frame are assumed to have
+    /// simulated identically.
+    fn checksum(&self, state: &Self::State) -> u64;
+}
+
+/// Returned by [`SyncTestSession::advance_frame`] when a re-simulated frame's
+/// checksum does not match the checksum recorded the first time that frame
+/// was simulated. This indicates the simulation is not deterministic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DesyncError {
+    /// The first frame whose recomputed checksum diverged.
+    pub frame: Frame,
+    /// The checksum that was recorded when `frame` was originally simulated.
+    pub expected_checksum: u64,
+    /// The checksum recomputed after reloading state and replaying inputs.
+    pub actual_checksum: u64,
+}
+
+impl fmt::Display for DesyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "detected desync at frame {}: expected checksum {:x}, got {:x}",
+            self.frame, self.expected_checksum, self.actual_checksum
+        )
+    }
+}
+
+impl std::error::Error for DesyncError {}
+
+/// A single-process session that forces a rollback every frame and replays
+/// the last `check_distance` confirmed frames to verify the simulation
+/// re-produces bit-identical state.
+///
+/// This turns a single-process run into a determinism fuzzer: desync bugs in
+/// save/load or simulation code surface immediately instead of only showing
+/// up over the network.
+pub struct SyncTestSession<T, C, const N: usize = MAX_ROLLBACK_FRAMES>
+where
+    T: bytemuck::Zeroable + Clone + PartialEq,
+    C: SyncTestCallbacks<T>,
+{
+    callbacks: C,
+    input_queue: InputQueue<T, N>,
+    check_distance: usize,
+    current_frame: Frame,
+    checksums: HashMap<Frame, u64>,
+    saved_states: HashMap<Frame, C::State>,
+}
+
+impl<T, C, const N: usize> SyncTestSession<T, C, N>
+where
+    T: bytemuck::Zeroable + Clone + PartialEq,
+    C: SyncTestCallbacks<T>,
+{
+    /// Creates a new sync test session that re-simulates the last
+    /// `check_distance` confirmed frames on every advance.
+    pub fn new(callbacks: C, check_distance: usize) -> Self {
+        assert!(check_distance > 0, "check_distance must be at least 1");
+        assert!(
+            check_distance < N,
+            "check_distance must be smaller than the input queue's capacity"
+        );
+        Self {
+            callbacks,
+            input_queue: InputQueue::new(0),
+            check_distance,
+            current_frame: 0,
+            checksums: HashMap::new(),
+            saved_states: HashMap::new(),
+        }
+    }
+
+    /// Advances the simulation by one frame using `input`, then replays the
+    /// last `check_distance` confirmed frames from a reloaded state and
+    /// verifies every recomputed checksum matches what was recorded the
+    /// first time that frame ran.
+    pub fn advance_frame(&mut self, input: T) -> Result<(), DesyncError> {
+        let frame = self.current_frame;
+        self.input_queue.add_input(FrameInput { frame, input });
+
+        self.callbacks
+            .advance_frame(&self.input_queue.get_confirmed_input(frame).unwrap().input);
+        let state = self.callbacks.save_state();
+        let checksum = self.callbacks.checksum(&state);
+        self.checksums.insert(frame, checksum);
+        self.saved_states.insert(frame, state);
+
+        let result = self.verify_last_frames(frame);
+
+        self.prune_history(frame);
+        self.current_frame += 1;
+        result
+    }
+
+    fn verify_last_frames(&mut self, frame: Frame) -> Result<(), DesyncError> {
+        let start = frame - Frame::try_from(self.check_distance).unwrap();
+        if start < 0 {
+            return Ok(());
+        }
+        let Some(state) = self.saved_states.get(&start).cloned() else {
+            // The starting frame has already aged out of the queue. Nothing
+            // to verify this time around.
+            return Ok(());
+        };
+
+        self.callbacks.load_state(&state);
+
+        let mut result = Ok(());
+        for replay_frame in (start + 1)..=frame {
+            let Some(input) = self.input_queue.get_confirmed_input(replay_frame) else {
+                break;
+            };
+            self.callbacks.advance_frame(&input.input);
+            let replayed_state = self.callbacks.save_state();
+            let actual_checksum = self.callbacks.checksum(&replayed_state);
+            let expected_checksum = *self.checksums.get(&replay_frame).unwrap();
+            if result.is_ok() && actual_checksum != expected_checksum {
+                result = Err(DesyncError {
+                    frame: replay_frame,
+                    expected_checksum,
+                    actual_checksum,
+                });
+            }
+        }
+
+        // Restore the canonical state so the next call to advance_frame
+        // continues from the real trajectory rather than the replay.
+        let canonical = self.saved_states.get(&frame).unwrap().clone();
+        self.callbacks.load_state(&canonical);
+
+        result
+    }
+
+    fn prune_history(&mut self, frame: Frame) {
+        let cutoff = frame - Frame::try_from(self.check_distance).unwrap();
+        self.checksums.retain(|&f, _| f >= cutoff);
+        self.saved_states.retain(|&f, _| f >= cutoff);
+
+        // Free the ring buffer slots for anything older than what a future
+        // verify pass could still need, or the fixed-size `InputQueue` never
+        // frees capacity and `length` grows past `N` forever.
+        if cutoff >= 0 {
+            self.input_queue.discard_confirmed_frames(cutoff);
+        }
+    }
+}